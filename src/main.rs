@@ -1,81 +1,186 @@
-use reqwest::Client;
-use std::future::Future;
+mod file_utils;
+mod models;
+mod pipeline;
+mod report;
+
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures::StreamExt;
 use tokio::task;
-use tokio::sync::mpsc;
-use log::{info, warn, error};
-use serde::Serialize;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::{sleep, Instant};
+use log::{info, error, warn};
 use serde_json::json;
 use std::fs::File;
 use std::io::Write;
-use std::env;
-use std::fmt::Debug;
+
+use models::{ModelProvider, OpenAIProvider, ProviderError};
+use models::provider::Completion;
+use pipeline::Pipeline;
+use report::{benchmark_swarm, ReportConfig};
+
+/// Resilience knobs for [`concurrent_swarm`].
+#[derive(Debug, Clone)]
+pub struct SwarmConfig {
+    /// Maximum number of requests in flight at once.
+    pub max_concurrency: usize,
+    /// Token-bucket rate limit, in requests per minute. `None` disables it.
+    pub requests_per_minute: Option<u32>,
+    /// Maximum number of retries for a retryable error (total attempts is
+    /// `max_retries + 1`).
+    pub max_retries: u32,
+}
+
+impl Default for SwarmConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 8,
+            requests_per_minute: None,
+            max_retries: 3,
+        }
+    }
+}
+
+/// A simple token-bucket rate limiter shared across swarm tasks.
+struct RateLimiter {
+    inner: Mutex<BucketState>,
+    capacity: f64,
+    refill_per_sec: f64,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    fn new(requests_per_minute: u32) -> Self {
+        let capacity = requests_per_minute.max(1) as f64;
+        Self {
+            inner: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+            capacity,
+            refill_per_sec: capacity / 60.0,
+        }
+    }
+
+    /// Block until a token is available, then consume it.
+    async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.inner.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+                state.last_refill = now;
+
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    return;
+                }
+                // Seconds until the next whole token is available.
+                (1.0 - state.tokens) / self.refill_per_sec
+            };
+            sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
 
 /// General-purpose concurrent swarm executor.
 ///
-/// This function takes any async callable (a function or closure), runs it concurrently `N` times,
-/// and returns a vector of results.
+/// This function drives a [`ModelProvider`] `n` times concurrently with the
+/// same `system`/`task` prompts, logs each result as a JSON line, and returns a
+/// vector of results. Because the provider is passed as an
+/// `Arc<dyn ModelProvider>`, the same swarm can be pointed at any backend —
+/// OpenAI, Anthropic, or an OpenAI-compatible gateway — without changing this
+/// code.
 ///
 /// # Arguments
 ///
-/// * `callable` - An async function or closure that takes a `Client` and returns a `Future`.
-/// * `n` - The number of times to execute the callable concurrently.
-/// * `client` - The HTTP client instance used to make requests.
-/// * `output_file` - The path to the file where the results will be logged as JSON.
+/// * `provider` - The model backend to drive.
+/// * `system` - The system prompt shared by every task.
+/// * `task` - The user task shared by every task.
+/// * `n` - The number of times to execute the provider concurrently.
+/// * `output_file` - The path to the file where results are logged as JSON.
+/// * `stream` - When `true`, each task consumes the provider's SSE stream and
+///   appends its incremental `delta` lines to the log once the stream has
+///   completed successfully, in addition to the final response.
+/// * `config` - Concurrency ceiling, rate limit, and retry policy.
 ///
-/// # Returns
-///
-/// A `Vec<Result<T, reqwest::Error>>` where each `Result` contains either the successful output
-/// or an error.
+/// At most `config.max_concurrency` tasks are in flight at once (enforced with
+/// a [`Semaphore`]); requests are paced by an optional token-bucket rate
+/// limiter; and retryable errors (HTTP 429/5xx, transport failures) are retried
+/// up to `config.max_retries` times with exponential backoff plus jitter,
+/// honoring a `Retry-After` header when present. The attempt count is recorded
+/// per task in the log.
 ///
-/// # Example
+/// # Returns
 ///
-/// ```rust
-/// let client = Client::new();
-/// let results = concurrent_swarm(call_openai_api, 5, client, "responses.json").await;
-/// ```
-pub async fn concurrent_swarm<F, Fut, T>(
-    callable: F,
+/// A `Vec<Result<Completion, ProviderError>>`, one entry per task.
+pub async fn concurrent_swarm(
+    provider: Arc<dyn ModelProvider>,
+    system: &str,
+    task: &str,
     n: usize,
-    client: Arc<Client>,
     output_file: &str,
-) -> Vec<Result<T, reqwest::Error>>
-where
-    F: Fn(Arc<Client>) -> Fut + Send + Sync + 'static + Copy,
-    Fut: Future<Output = Result<T, reqwest::Error>> + Send,
-    T: Send + 'static + Debug + Serialize, // Ensure T implements Serialize
-{
+    stream: bool,
+    config: &SwarmConfig,
+) -> Vec<Result<Completion, ProviderError>> {
     let (tx, mut rx) = mpsc::channel(n);
     let mut results = Vec::with_capacity(n);
 
     let file = Arc::new(Mutex::new(File::create(output_file).unwrap()));
+    let semaphore = Arc::new(Semaphore::new(config.max_concurrency.max(1)));
+    let limiter = config
+        .requests_per_minute
+        .map(|rpm| Arc::new(RateLimiter::new(rpm)));
+    let max_retries = config.max_retries;
 
     // Spawn N tasks
     for i in 0..n {
-        let callable = callable.clone();
-        let client = Arc::clone(&client);
+        let provider = Arc::clone(&provider);
         let tx = tx.clone();
         let file = Arc::clone(&file);
+        let semaphore = Arc::clone(&semaphore);
+        let limiter = limiter.clone();
+        let system = system.to_string();
+        let task_prompt = task.to_string();
 
         task::spawn(async move {
-            let result = callable(client).await;
+            let (result, attempts) = execute_with_retry(
+                provider.as_ref(),
+                &system,
+                &task_prompt,
+                i + 1,
+                stream,
+                max_retries,
+                &semaphore,
+                limiter.as_deref(),
+                &file,
+            )
+            .await;
+
             let log_entry = match &result {
-                Ok(response) => json!({
+                Ok(completion) => json!({
                     "task": i + 1,
                     "status": "success",
-                    "response": response,
+                    "attempts": attempts,
+                    "response": completion,
                 }),
                 Err(e) => json!({
                     "task": i + 1,
                     "status": "error",
-                    "error": format!("{:?}", e),
+                    "attempts": attempts,
+                    "error": format!("{}", e),
                 }),
             };
 
             // Log the entry after the async block completes to avoid holding the MutexGuard during await
             {
                 let mut file = file.lock().unwrap();
-                writeln!(file, "{}", log_entry.to_string()).unwrap();
+                writeln!(file, "{}", log_entry).unwrap();
             }
 
             if tx.send(result).await.is_err() {
@@ -93,82 +198,381 @@ where
     results
 }
 
-/// Function to call the OpenAI API
-///
-/// This function is an example of how you can define a callable function to be used with
-/// `concurrent_swarm`. It allows passing the model name, system prompt, and task (user message)
-/// dynamically.
+/// Run one swarm task, bounded by the semaphore and rate limiter, retrying
+/// retryable failures with exponential backoff + jitter. Returns the final
+/// result and the number of attempts made.
+#[allow(clippy::too_many_arguments)]
+async fn execute_with_retry(
+    provider: &dyn ModelProvider,
+    system: &str,
+    task: &str,
+    task_id: usize,
+    stream: bool,
+    max_retries: u32,
+    semaphore: &Semaphore,
+    limiter: Option<&RateLimiter>,
+    file: &Arc<Mutex<File>>,
+) -> (Result<Completion, ProviderError>, u32) {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+
+        if let Some(limiter) = limiter {
+            limiter.acquire().await;
+        }
+
+        let result = {
+            // Hold the concurrency permit only for the duration of the request,
+            // releasing it before any backoff sleep so other tasks proceed.
+            let _permit = semaphore.acquire().await.expect("semaphore is never closed");
+            if stream {
+                run_streamed(provider, system, task, task_id, file).await
+            } else {
+                provider.complete(system, task).await
+            }
+        };
+
+        match result {
+            Ok(completion) => return (Ok(completion), attempt),
+            Err(e) => {
+                if e.is_retryable() && attempt <= max_retries {
+                    let delay = backoff_delay(attempt, e.retry_after(), task_id as u64);
+                    warn!(
+                        "Task {} attempt {} failed ({}); retrying in {:?}",
+                        task_id, attempt, e, delay
+                    );
+                    sleep(delay).await;
+                    continue;
+                }
+                return (Err(e), attempt);
+            }
+        }
+    }
+}
+
+/// Exponential backoff with jitter: `base * 2^(attempt-1)` capped at 30s, plus
+/// up to 50% random jitter. A server-supplied `Retry-After` takes precedence,
+/// but is clamped to the same 30s ceiling so a large or hostile header value
+/// can't pin a task asleep.
 ///
-/// # Arguments
+/// `task_id` seeds the jitter so that tasks failing in the same instant — the
+/// 429-storm case retries target — still draw distinct delays.
+fn backoff_delay(attempt: u32, retry_after: Option<u64>, task_id: u64) -> Duration {
+    if let Some(secs) = retry_after {
+        return Duration::from_millis((secs.saturating_mul(1000)).min(30_000));
+    }
+
+    let base_ms = 500u64;
+    let capped = base_ms.saturating_mul(1 << (attempt - 1).min(6)).min(30_000);
+    let seed = task_id
+        .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ u64::from(attempt);
+    let jitter = (capped as f64 * 0.5 * jitter_fraction(seed)) as u64;
+    Duration::from_millis(capped + jitter)
+}
+
+/// A pseudo-random fraction in `[0, 1)`, used to spread retries and avoid a
+/// thundering herd without pulling in an RNG crate.
 ///
-/// * `client` - The HTTP client instance.
-/// * `model` - The name of the OpenAI model to use (e.g., "gpt-4o-mini").
-/// * `system_prompt` - The system prompt to set the behavior of the assistant.
-/// * `user_task` - The task or question you want to ask the assistant.
+/// The per-task/attempt `seed` is mixed with the system clock through the
+/// splitmix64 finalizer. Seeding from the task id matters because the clock
+/// alone only offers coarse buckets, so two tasks re-entering backoff at nearly
+/// the same instant would otherwise draw nearly-identical jitter — exactly the
+/// case where de-correlated delays are needed.
+fn jitter_fraction(seed: u64) -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    splitmix_fraction(nanos ^ seed)
+}
+
+/// The splitmix64 finalizer, mapped to a uniform double in `[0, 1)`.
 ///
-/// # Returns
+/// Split out from [`jitter_fraction`] so the seed-dependence can be asserted
+/// without the system clock in the way: two task seeds at the same instant must
+/// map to different fractions.
+fn splitmix_fraction(mut z: u64) -> f64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    // Take the top 53 bits for a uniform double in [0, 1).
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Consume a provider's streaming completion and return the reassembled
+/// [`Completion`].
 ///
-/// A `Result<String, reqwest::Error>` with the response text or an error.
-async fn call_openai_api(
-    client: Arc<Client>,
-    model: &str,
-    system_prompt: &str,
-    user_task: &str,
-) -> Result<String, reqwest::Error> {
-    let url = "https://api.openai.com/v1/chat/completions";
-    let api_key = env::var("OPENAI_API_KEY").expect("API key not found in environment variables");
-
-    let request_body = format!(
-        r#"{{
-            "model": "{}",
-            "messages": [
-                {{"role": "system", "content": "{}"}},
-                {{"role": "user", "content": "{}"}}
-            ]
-        }}"#,
-        model, system_prompt, user_task
-    );
-
-    let response = client
-        .post(url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .body(request_body)
-        .send()
-        .await?;
-
-    let text = response.text().await?;
-    Ok(text)
+/// Deltas are buffered and flushed to the shared log only once the stream
+/// completes successfully. Because `run_streamed` runs inside the retry loop, a
+/// mid-stream transport error aborts the attempt; flushing eagerly would leave
+/// the partial deltas of a failed attempt interleaved with those of the retry,
+/// so a consumer concatenating `delta` lines would see duplicated text.
+async fn run_streamed(
+    provider: &dyn ModelProvider,
+    system: &str,
+    task: &str,
+    task_id: usize,
+    file: &Arc<Mutex<File>>,
+) -> Result<Completion, ProviderError> {
+    let mut stream = provider.complete_stream(system, task).await?;
+    let mut content = String::new();
+    let mut deltas = Vec::new();
+
+    while let Some(delta) = stream.next().await {
+        // Propagate errors before any log line is written, so a failed attempt
+        // leaves no trace for the retry to duplicate.
+        let delta = delta?;
+        content.push_str(&delta);
+        deltas.push(delta);
+    }
+
+    {
+        let mut file = file.lock().unwrap();
+        for delta in &deltas {
+            writeln!(file, "{}", json!({ "task": task_id, "delta": delta })).unwrap();
+        }
+    }
+
+    Ok(Completion {
+        provider: provider.provider_name().to_string(),
+        model: provider.model().to_string(),
+        content,
+        prompt_tokens: None,
+        completion_tokens: None,
+    })
 }
 
 #[tokio::main]
 async fn main() {
     env_logger::init(); // Initialize the logger
 
-    let client = Arc::new(Client::new());
-
     info!("Starting concurrent OpenAI API requests");
 
     // Define the model, system prompt, and user task
-    let model = "gpt-4o-mini";
     let system_prompt = "You are a helpful assistant.";
     let user_task = "Who won the world series in 2020?";
 
     // Output file for logging the responses
     let output_file = "responses.json";
 
-    // Create a closure that wraps the call_openai_api function with the provided parameters
-    let task = |client: Arc<Client>| call_openai_api(client, model, system_prompt, user_task);
+    let provider: Arc<dyn ModelProvider> = match OpenAIProvider::new("gpt-4o-mini") {
+        Ok(provider) => Arc::new(provider),
+        Err(e) => {
+            error!("Failed to build provider: {}", e);
+            return;
+        }
+    };
+
+    // `--pipeline <file>` runs a declarative DAG workflow from a JSON document
+    // instead of a flat fan-out, feeding each step's output into its
+    // dependents. The graph is parsed, topologically layered, and executed.
+    let mut args = std::env::args();
+    if let Some(path) = args.by_ref().skip_while(|a| a != "--pipeline").nth(1) {
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Failed to read pipeline file {}: {}", path, e);
+                return;
+            }
+        };
+        let pipeline = match Pipeline::from_json(&json) {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                error!("Failed to parse pipeline: {}", e);
+                return;
+            }
+        };
+        match pipeline.execute(provider).await {
+            Ok(outcomes) => {
+                for (uuid, outcome) in outcomes {
+                    info!("Pipeline step '{}': {:?}", uuid, outcome);
+                }
+            }
+            Err(e) => error!("Pipeline could not be executed: {}", e),
+        }
+        return;
+    }
+
+    // `--benchmark` runs the measurable harness and writes a JSON report
+    // instead of the ad-hoc response log, giving a reproducible
+    // performance-regression signal across models, providers, and concurrency.
+    if std::env::args().any(|a| a == "--benchmark") {
+        let report_config = ReportConfig {
+            provider: provider.provider_name().to_string(),
+            model: provider.model().to_string(),
+            ..Default::default()
+        };
+        let report = benchmark_swarm(provider, system_prompt, user_task, 4, &report_config).await;
+        info!("Benchmark run {} complete: {:?}", report.run_id, report.stats);
+        return;
+    }
 
     // Run the concurrent swarm
-    let results = concurrent_swarm(task, 4, client, output_file).await;
+    let config = SwarmConfig::default();
+    let results =
+        concurrent_swarm(provider, system_prompt, user_task, 4, output_file, false, &config).await;
 
     for (i, result) in results.into_iter().enumerate() {
         match result {
-            Ok(response) => info!("Request {}: Success - {:?}", i + 1, response),
-            Err(e) => error!("Request {}: Failed - {:?}", i + 1, e),
+            Ok(completion) => info!("Request {}: Success - {:?}", i + 1, completion),
+            Err(e) => error!("Request {}: Failed - {}", i + 1, e),
         }
     }
 
     info!("All tasks completed.");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use models::provider::CompletionStream;
+
+    /// A provider whose stream yields a fixed sequence of deltas, so the
+    /// reassembly and per-delta logging in [`run_streamed`] can be exercised
+    /// without any network I/O.
+    struct StreamStub;
+
+    #[async_trait]
+    impl ModelProvider for StreamStub {
+        fn provider_name(&self) -> &str {
+            "stub"
+        }
+
+        fn model(&self) -> &str {
+            "stub-model"
+        }
+
+        async fn complete(&self, _system: &str, _task: &str) -> Result<Completion, ProviderError> {
+            unreachable!("run_streamed only uses complete_stream")
+        }
+
+        async fn complete_stream(
+            &self,
+            _system: &str,
+            _task: &str,
+        ) -> Result<CompletionStream, ProviderError> {
+            let deltas = vec![Ok("Hel".to_string()), Ok("lo".to_string())];
+            Ok(Box::pin(futures::stream::iter(deltas)))
+        }
+    }
+
+    #[tokio::test]
+    async fn run_streamed_reassembles_and_logs_deltas() {
+        let path = std::env::temp_dir().join(format!("swarm-run-streamed-{}.json", std::process::id()));
+        let file = Arc::new(Mutex::new(File::create(&path).unwrap()));
+
+        let completion = run_streamed(&StreamStub, "sys", "task", 1, &file)
+            .await
+            .expect("stub stream never errors");
+
+        assert_eq!(completion.content, "Hello");
+        assert_eq!(completion.provider, "stub");
+        assert_eq!(completion.model, "stub-model");
+
+        let logged = std::fs::read_to_string(&path).unwrap();
+        assert!(logged.contains("\"delta\":\"Hel\""));
+        assert!(logged.contains("\"delta\":\"lo\""));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// A provider that fails every call with a configurable error, counting how
+    /// many times it was invoked.
+    struct AlwaysFail {
+        retryable: bool,
+        calls: Arc<Mutex<u32>>,
+    }
+
+    #[async_trait]
+    impl ModelProvider for AlwaysFail {
+        fn provider_name(&self) -> &str {
+            "fail"
+        }
+
+        fn model(&self) -> &str {
+            "fail-model"
+        }
+
+        async fn complete(&self, _system: &str, _task: &str) -> Result<Completion, ProviderError> {
+            *self.calls.lock().unwrap() += 1;
+            if self.retryable {
+                Err(ProviderError::Api {
+                    status: 503,
+                    body: "unavailable".to_string(),
+                    retry_after: None,
+                })
+            } else {
+                Err(ProviderError::Parse("bad body".to_string()))
+            }
+        }
+    }
+
+    fn scratch_file() -> Arc<Mutex<File>> {
+        let path = std::env::temp_dir()
+            .join(format!("swarm-retry-{}.json", std::process::id()));
+        Arc::new(Mutex::new(File::create(path).unwrap()))
+    }
+
+    #[test]
+    fn backoff_caps_exponential_path_at_30s() {
+        // A large attempt drives the exponential term past the 30s ceiling; the
+        // capped base is 30s and jitter adds at most another 50%.
+        let delay = backoff_delay(20, None, 7);
+        let millis = delay.as_millis();
+        assert!(millis >= 30_000, "base should be capped at 30s, got {millis}");
+        assert!(millis <= 45_000, "jitter should stay within 50%, got {millis}");
+    }
+
+    #[test]
+    fn backoff_clamps_retry_after_to_30s() {
+        // A hostile/huge Retry-After is clamped to the same 30s ceiling...
+        assert_eq!(backoff_delay(1, Some(600), 1).as_millis(), 30_000);
+        // ...while a modest one is honored verbatim, with no jitter.
+        assert_eq!(backoff_delay(1, Some(5), 1).as_millis(), 5_000);
+    }
+
+    #[test]
+    fn distinct_task_seeds_yield_distinct_jitter_at_same_instant() {
+        // Hold the "clock" fixed and vary only the per-task seed, the exact
+        // 429-storm case the seeding targets.
+        let clock = 123_456_789u64;
+        let seed_a = 1u64.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let seed_b = 2u64.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        assert_ne!(
+            splitmix_fraction(clock ^ seed_a),
+            splitmix_fraction(clock ^ seed_b)
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_loop_exhausts_max_retries_on_persistent_retryable_error() {
+        // A small retry budget keeps the real backoff sleeps brief.
+        let calls = Arc::new(Mutex::new(0));
+        let provider = AlwaysFail { retryable: true, calls: Arc::clone(&calls) };
+        let semaphore = Semaphore::new(1);
+        let file = scratch_file();
+
+        let (result, attempts) =
+            execute_with_retry(&provider, "s", "t", 1, false, 2, &semaphore, None, &file).await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 3, "max_retries + 1 total attempts");
+        assert_eq!(*calls.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_loop_does_not_retry_non_retryable_error() {
+        let calls = Arc::new(Mutex::new(0));
+        let provider = AlwaysFail { retryable: false, calls: Arc::clone(&calls) };
+        let semaphore = Semaphore::new(1);
+        let file = scratch_file();
+
+        let (result, attempts) =
+            execute_with_retry(&provider, "s", "t", 1, false, 3, &semaphore, None, &file).await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1, "non-retryable error returns after one attempt");
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+}
@@ -1,11 +1,20 @@
-use log::{info, error, warn, debug};
+use async_trait::async_trait;
+use log::{debug, info};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::env;
-use std::error::Error;
+
+use super::provider::{Completion, CompletionStream, ModelProvider, ProviderError};
+use super::sse::{decode_sse, LineAction};
+
+/// Default Anthropic API version header value.
+pub const DEFAULT_ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Default Anthropic API base URL; `/messages` is appended per request.
+pub const DEFAULT_ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com/v1";
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Message {
@@ -41,117 +50,214 @@ struct ApiRequest {
     tool_choice: Option<Value>,
 }
 
-// Utility function to fetch the API key from the environment
-fn get_api_key() -> Result<String, Box<dyn Error>> {
-    match env::var("ANTHROPIC_API_KEY") {
-        Ok(key) => {
-            debug!("API key found in environment.");
-            Ok(key)
-        },
-        Err(_) => {
-            error!("API key not found in environment variable ANTHROPIC_API_KEY.");
-            Err(Box::from("API key not found in environment variable ANTHROPIC_API_KEY."))
+/// Anthropic messages backend.
+pub struct AnthropicProvider {
+    client: Client,
+    api_key: String,
+    model: String,
+    base_url: String,
+    api_version: String,
+    max_tokens: u32,
+}
+
+impl AnthropicProvider {
+    /// Build a provider, reading the key from `ANTHROPIC_API_KEY`.
+    pub fn new(model: impl Into<String>) -> Result<Self, ProviderError> {
+        let api_key = env::var("ANTHROPIC_API_KEY")
+            .map_err(|_| ProviderError::MissingApiKey("ANTHROPIC_API_KEY".to_string()))?;
+        Ok(Self {
+            client: Client::new(),
+            api_key,
+            model: model.into(),
+            base_url: DEFAULT_ANTHROPIC_BASE_URL.to_string(),
+            api_version: DEFAULT_ANTHROPIC_VERSION.to_string(),
+            max_tokens: 1024,
+        })
+    }
+
+    /// Override the base URL (e.g. a proxy or gateway).
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Override the `anthropic-version` header.
+    pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+        self.api_version = api_version.into();
+        self
+    }
+
+    /// Override the `max_tokens` ceiling for each request.
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Build the standard auth/version headers shared by both call paths.
+    fn headers(&self) -> Result<HeaderMap, ProviderError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-api-key",
+            HeaderValue::from_str(&self.api_key)
+                .map_err(|err| ProviderError::Parse(err.to_string()))?,
+        );
+        headers.insert(
+            "anthropic-version",
+            HeaderValue::from_str(&self.api_version)
+                .map_err(|err| ProviderError::Parse(err.to_string()))?,
+        );
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(headers)
+    }
+
+    /// Build the request body, optionally flipping on streaming.
+    fn build_request(&self, system: &str, task: &str, stream: bool) -> ApiRequest {
+        ApiRequest {
+            model: self.model.clone(),
+            max_tokens: self.max_tokens,
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: Value::String(task.to_string()),
+            }],
+            stop_sequences: None,
+            temperature: None,
+            top_k: None,
+            top_p: None,
+            stream: Some(stream),
+            metadata: None,
+            tools: None,
+            system: Some(system.to_string()),
+            tool_choice: None,
         }
     }
 }
 
-async fn call_anthropic_api(api_version: &str, request: ApiRequest) -> Result<ApiResponse, Box<dyn Error>> {
-    // Fetch the API key
-    let api_key = get_api_key()?;
-    
-    // Initialize the HTTP client
-    let client = Client::new();
-    
-    // Prepare the headers
-    let mut headers = HeaderMap::new();
-    headers.insert("x-api-key", HeaderValue::from_str(&api_key)?);
-    headers.insert("anthropic-version", HeaderValue::from_str(api_version)?);
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-    // Convert the request to JSON
-    let request_body = serde_json::to_string(&request)?;
-    debug!("Request Body: {}", request_body);
-
-    // Send the POST request
-    let response = client
-        .post("https://api.anthropic.com/v1/messages")
-        .headers(headers)
-        .body(request_body)
-        .send()
-        .await;
-
-    // Handle potential errors
-    let response = match response {
-        Ok(res) => {
-            info!("API call successful, status: {}", res.status());
-            res
-        },
-        Err(err) => {
-            error!("API call failed: {}", err);
-            return Err(Box::new(err));
-        }
-    };
+#[async_trait]
+impl ModelProvider for AnthropicProvider {
+    fn provider_name(&self) -> &str {
+        "anthropic"
+    }
 
-    // Parse the response
-    let response_body = response.text().await?;
-    debug!("Response Body: {}", response_body);
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, system: &str, task: &str) -> Result<Completion, ProviderError> {
+        let headers = self.headers()?;
+        let request = self.build_request(system, task, false);
+
+        let request_body =
+            serde_json::to_string(&request).map_err(|err| ProviderError::Parse(err.to_string()))?;
+        debug!("Request Body: {}", request_body);
 
-    let api_response: ApiResponse = match serde_json::from_str(&response_body) {
-        Ok(res) => res,
-        Err(err) => {
-            error!("Failed to parse API response: {}", err);
-            return Err(Box::new(err));
+        let response = self
+            .client
+            .post(format!("{}/messages", self.base_url))
+            .headers(headers)
+            .body(request_body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        info!("API call status: {}", status);
+        let retry_after = super::provider::parse_retry_after(response.headers());
+        let body = response.text().await?;
+        debug!("Response Body: {}", body);
+        if !status.is_success() {
+            return Err(ProviderError::Api {
+                status: status.as_u16(),
+                body,
+                retry_after,
+            });
         }
-    };
 
-    // Log usage data
-    if let Some(usage) = api_response.usage.get("input_tokens") {
-        info!("Input Tokens Used: {}", usage);
+        let api_response: ApiResponse =
+            serde_json::from_str(&body).map_err(|err| ProviderError::Parse(err.to_string()))?;
+
+        let content = api_response
+            .content
+            .iter()
+            .filter_map(|block| block.get("text").cloned())
+            .collect::<Vec<_>>()
+            .join("");
+
+        Ok(Completion {
+            provider: "anthropic".to_string(),
+            model: api_response.model,
+            content,
+            prompt_tokens: api_response.usage.get("input_tokens").copied(),
+            completion_tokens: api_response.usage.get("output_tokens").copied(),
+        })
     }
-    if let Some(usage) = api_response.usage.get("output_tokens") {
-        info!("Output Tokens Generated: {}", usage);
+
+    async fn complete_stream(
+        &self,
+        system: &str,
+        task: &str,
+    ) -> Result<CompletionStream, ProviderError> {
+        let headers = self.headers()?;
+        let request = self.build_request(system, task, true);
+        let request_body =
+            serde_json::to_string(&request).map_err(|err| ProviderError::Parse(err.to_string()))?;
+
+        let response = self
+            .client
+            .post(format!("{}/messages", self.base_url))
+            .headers(headers)
+            .body(request_body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = super::provider::parse_retry_after(response.headers());
+            let body = response.text().await?;
+            return Err(ProviderError::Api {
+                status: status.as_u16(),
+                body,
+                retry_after,
+            });
+        }
+
+        Ok(decode_sse(response.bytes_stream(), parse_sse_line))
     }
+}
 
-    Ok(api_response)
+/// Interpret one Anthropic SSE line: emit the `delta.text` of each
+/// `content_block_delta` event and stop on `message_stop`; everything else
+/// (other event types, the `event:` lines, blanks) is skipped.
+fn parse_sse_line(line: &str) -> Result<LineAction, ProviderError> {
+    let Some(data) = line.strip_prefix("data:") else {
+        return Ok(LineAction::Skip);
+    };
+    let value: Value =
+        serde_json::from_str(data.trim()).map_err(|err| ProviderError::Parse(err.to_string()))?;
+    match value["type"].as_str() {
+        Some("content_block_delta") => {
+            if let Some(delta) = value["delta"]["text"].as_str() {
+                if !delta.is_empty() {
+                    return Ok(LineAction::Emit(delta.to_string()));
+                }
+            }
+            Ok(LineAction::Skip)
+        }
+        Some("message_stop") => Ok(LineAction::Stop),
+        _ => Ok(LineAction::Skip),
+    }
 }
 
-// // Example usage
-// #[tokio::main]
-// async fn main() {
-//     // Initialize logger (log to console for this example)
-//     env_logger::init();
-
-//     // Define your API version
-//     let api_version = "2023-06-01";
-
-//     // Create the API request
-//     let request = ApiRequest {
-//         model: "claude-3-5-sonnet-20240620".to_string(),
-//         max_tokens: 1024,
-//         messages: vec![
-//             Message {
-//                 role: "user".to_string(),
-//                 content: serde_json::json!("Hello, Claude"),
-//             },
-//         ],
-//         stop_sequences: None,
-//         temperature: Some(0.7),
-//         top_k: Some(50),
-//         top_p: Some(0.95),
-//         stream: Some(false),
-//         metadata: None,
-//         tools: None,
-//         system: None,
-//         tool_choice: None,
-//     };
-
-//     // Call the API
-//     match call_anthropic_api(api_version, request).await {
-//         Ok(response) => {
-//             info!("Received response: {:?}", response);
-//         },
-//         Err(err) => {
-//             error!("Failed to get response: {}", err);
-//         }
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::super::sse::test_support::decode_in_chunks;
+    use super::*;
+
+    #[tokio::test]
+    async fn stops_on_message_stop() {
+        let body = "event: content_block_delta\n\
+                    data: {\"type\":\"content_block_delta\",\"delta\":{\"text\":\"a\"}}\n\n\
+                    data: {\"type\":\"message_stop\"}\n\
+                    data: {\"type\":\"content_block_delta\",\"delta\":{\"text\":\"after\"}}\n";
+        let deltas = decode_in_chunks(body, 8, parse_sse_line).await;
+        assert_eq!(deltas, vec!["a".to_string()]);
+    }
+}
@@ -0,0 +1,8 @@
+pub mod anthropic_model;
+pub mod openai;
+pub mod provider;
+mod sse;
+
+pub use anthropic_model::AnthropicProvider;
+pub use openai::OpenAIProvider;
+pub use provider::{Completion, ModelProvider, ProviderError};
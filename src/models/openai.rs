@@ -1,77 +1,219 @@
+use async_trait::async_trait;
+use log::debug;
 use reqwest::Client;
-use std::future::Future;
-use std::sync::{Arc, Mutex};
-use tokio::task;
-use tokio::sync::mpsc;
-use log::{info, warn, error};
-use serde::Serialize;
-use serde_json::json;
-use std::fs::File;
-use std::io::Write;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::env;
-use std::fmt::Debug;
-
-
-// Utility function to fetch the API key from the environment
-fn get_api_key() -> Result<String, Box<dyn Error>> {
-    match env::var("OPENAI_API_KEY") {
-        Ok(key) => {
-            debug!("API key found in environment.");
-            Ok(key)
-        },
-        Err(_) => {
-            error!("API key not found in environment variable OPENAI_API_KEY.");
-            Err(Box::from("API key not found in environment variable OPENAI_API_KEY."))
+
+use super::provider::{Completion, CompletionStream, ModelProvider, ProviderError};
+use super::sse::{decode_sse, LineAction};
+
+#[derive(Debug, Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseMessage {
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    message: ResponseMessage,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Usage {
+    prompt_tokens: Option<u64>,
+    completion_tokens: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Usage,
+}
+
+/// Default OpenAI API base URL; `/chat/completions` is appended per request.
+///
+/// Override it via [`OpenAIProvider::with_base_url`] to point at Azure,
+/// OpenRouter, or a local gateway that speaks the same wire format.
+pub const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// OpenAI (and OpenAI-compatible) chat-completions backend.
+pub struct OpenAIProvider {
+    client: Client,
+    api_key: String,
+    model: String,
+    base_url: String,
+}
+
+impl OpenAIProvider {
+    /// Build a provider, reading the key from `OPENAI_API_KEY`.
+    pub fn new(model: impl Into<String>) -> Result<Self, ProviderError> {
+        let api_key = env::var("OPENAI_API_KEY")
+            .map_err(|_| ProviderError::MissingApiKey("OPENAI_API_KEY".to_string()))?;
+        Ok(Self {
+            client: Client::new(),
+            api_key,
+            model: model.into(),
+            base_url: DEFAULT_OPENAI_BASE_URL.to_string(),
+        })
+    }
+
+    /// Override the base URL (e.g. an Azure or OpenRouter gateway).
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+}
+
+#[async_trait]
+impl ModelProvider for OpenAIProvider {
+    fn provider_name(&self) -> &str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, system: &str, task: &str) -> Result<Completion, ProviderError> {
+        let url = format!("{}/chat/completions", self.base_url);
+
+        let request = ChatRequest {
+            model: &self.model,
+            messages: vec![
+                ChatMessage { role: "system", content: system },
+                ChatMessage { role: "user", content: task },
+            ],
+            stream: None,
+        };
+
+        let response = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&request)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let retry_after = super::provider::parse_retry_after(response.headers());
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(ProviderError::Api {
+                status: status.as_u16(),
+                body,
+                retry_after,
+            });
         }
+        debug!("Response Body: {}", body);
+
+        let parsed: ChatResponse = serde_json::from_str(&body)
+            .map_err(|err| ProviderError::Parse(err.to_string()))?;
+
+        let content = parsed
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::Parse("response contained no choices".to_string()))?
+            .message
+            .content;
+
+        Ok(Completion {
+            provider: "openai".to_string(),
+            model: self.model.clone(),
+            content,
+            prompt_tokens: parsed.usage.prompt_tokens,
+            completion_tokens: parsed.usage.completion_tokens,
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        system: &str,
+        task: &str,
+    ) -> Result<CompletionStream, ProviderError> {
+        let url = format!("{}/chat/completions", self.base_url);
+
+        let request = ChatRequest {
+            model: &self.model,
+            messages: vec![
+                ChatMessage { role: "system", content: system },
+                ChatMessage { role: "user", content: task },
+            ],
+            stream: Some(true),
+        };
+
+        let response = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&request)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = super::provider::parse_retry_after(response.headers());
+            let body = response.text().await?;
+            return Err(ProviderError::Api {
+                status: status.as_u16(),
+                body,
+                retry_after,
+            });
+        }
+
+        Ok(decode_sse(response.bytes_stream(), parse_sse_line))
     }
 }
 
+/// Interpret one OpenAI SSE line: pull the `content` delta out of each `data:`
+/// payload, skip blank lines and keep-alive comments, and stop on `[DONE]`.
+fn parse_sse_line(line: &str) -> Result<LineAction, ProviderError> {
+    let Some(data) = line.strip_prefix("data:") else {
+        return Ok(LineAction::Skip);
+    };
+    let data = data.trim();
+    if data == "[DONE]" {
+        return Ok(LineAction::Stop);
+    }
 
+    let value: Value =
+        serde_json::from_str(data).map_err(|err| ProviderError::Parse(err.to_string()))?;
+    if let Some(delta) = value["choices"][0]["delta"]["content"].as_str() {
+        if !delta.is_empty() {
+            return Ok(LineAction::Emit(delta.to_string()));
+        }
+    }
+    Ok(LineAction::Skip)
+}
 
-/// Function to call the OpenAI API
-///
-/// This function is an example of how you can define a callable function to be used with
-/// `concurrent_swarm`. It allows passing the model name, system prompt, and task (user message)
-/// dynamically.
-///
-/// # Arguments
-///
-/// * `client` - The HTTP client instance.
-/// * `model` - The name of the OpenAI model to use (e.g., "gpt-4o-mini").
-/// * `system_prompt` - The system prompt to set the behavior of the assistant.
-/// * `user_task` - The task or question you want to ask the assistant.
-///
-/// # Returns
-///
-/// A `Result<String, reqwest::Error>` with the response text or an error.
-pub async fn call_openai_api(
-    client: Arc<Client>,
-    model: &str,
-    system_prompt: &str,
-    user_task: &str,
-) -> Result<String, reqwest::Error> {
-    let url = "https://api.openai.com/v1/chat/completions";
-    let api_key = env::var("OPENAI_API_KEY").expect("API key not found in environment variables");
-
-    let request_body = format!(
-        r#"{{
-            "model": "{}",
-            "messages": [
-                {{"role": "system", "content": "{}"}},
-                {{"role": "user", "content": "{}"}}
-            ]
-        }}"#,
-        model, system_prompt, user_task
-    );
-
-    let response = client
-        .post(url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .body(request_body)
-        .send()
-        .await?;
-
-    let text = response.text().await?;
-    Ok(text)
+#[cfg(test)]
+mod tests {
+    use super::super::sse::test_support::decode_in_chunks;
+    use super::*;
+
+    #[tokio::test]
+    async fn stops_at_done_sentinel() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"a\"}}]}\n\
+                    data: [DONE]\n\
+                    data: {\"choices\":[{\"delta\":{\"content\":\"b\"}}]}\n";
+        let deltas = decode_in_chunks(body, 7, parse_sse_line).await;
+        assert_eq!(deltas, vec!["a".to_string()]);
+    }
 }
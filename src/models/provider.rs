@@ -0,0 +1,149 @@
+use async_trait::async_trait;
+use futures::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::pin::Pin;
+
+/// A completion returned by a [`ModelProvider`].
+///
+/// This is the provider-agnostic result type that the swarm executor logs and
+/// passes around, regardless of which backend actually produced it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Completion {
+    /// The provider that produced this completion (e.g. `"openai"`).
+    pub provider: String,
+    /// The model name that produced this completion.
+    pub model: String,
+    /// The generated text content.
+    pub content: String,
+    /// Prompt (input) tokens consumed, when the provider reports them.
+    pub prompt_tokens: Option<u64>,
+    /// Completion (output) tokens generated, when the provider reports them.
+    pub completion_tokens: Option<u64>,
+}
+
+/// Unified error type for every [`ModelProvider`].
+///
+/// This collapses the previous split between `reqwest::Error` and
+/// `Box<dyn Error>` paths into a single enum, so callers get one error surface
+/// no matter which backend they drive.
+#[derive(Debug)]
+pub enum ProviderError {
+    /// The API key was missing from the environment.
+    MissingApiKey(String),
+    /// The underlying HTTP request failed.
+    Http(reqwest::Error),
+    /// The response body could not be parsed as the expected shape.
+    Parse(String),
+    /// The provider returned a non-success HTTP status.
+    Api {
+        status: u16,
+        body: String,
+        /// Seconds requested by a `Retry-After` header, when present.
+        retry_after: Option<u64>,
+    },
+}
+
+impl ProviderError {
+    /// Whether retrying the request might succeed: HTTP 429, any 5xx, or a
+    /// transport-level failure such as a connection reset or timeout.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ProviderError::Api { status, .. } => *status == 429 || (500..600).contains(status),
+            ProviderError::Http(err) => {
+                err.is_timeout() || err.is_connect() || err.is_request()
+            }
+            _ => false,
+        }
+    }
+
+    /// The server-requested retry delay (seconds), if any.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            ProviderError::Api { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::MissingApiKey(var) => {
+                write!(f, "API key not found in environment variable {}", var)
+            }
+            ProviderError::Http(err) => write!(f, "HTTP request failed: {}", err),
+            ProviderError::Parse(msg) => write!(f, "failed to parse response: {}", msg),
+            ProviderError::Api { status, body, .. } => {
+                write!(f, "provider returned status {}: {}", status, body)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProviderError::Http(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(err: reqwest::Error) -> Self {
+        ProviderError::Http(err)
+    }
+}
+
+/// Parse a `Retry-After` response header into whole seconds.
+///
+/// Only the numeric-seconds form is honored; HTTP-date values are ignored.
+pub fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// A stream of incremental completion deltas.
+///
+/// Each item is a token (or token run) decoded from a `text/event-stream`
+/// response; errors are surfaced in-band so a mid-stream failure terminates the
+/// stream rather than being silently dropped.
+pub type CompletionStream = Pin<Box<dyn Stream<Item = Result<String, ProviderError>> + Send>>;
+
+/// A chat-completion backend.
+///
+/// Implementors carry their own [`reqwest::Client`], API key, model name, and
+/// base URL, so the same swarm can fan out across heterogeneous providers
+/// (OpenAI, Anthropic, Azure, OpenRouter, a local gateway, ...) behind a single
+/// `Arc<dyn ModelProvider>`.
+#[async_trait]
+pub trait ModelProvider: Send + Sync {
+    /// The provider's stable identifier (e.g. `"openai"`).
+    fn provider_name(&self) -> &str;
+
+    /// The model name this provider drives.
+    fn model(&self) -> &str;
+
+    /// Run a single buffered completion for `system` + `task`.
+    async fn complete(&self, system: &str, task: &str) -> Result<Completion, ProviderError>;
+
+    /// Run a streaming completion, yielding incremental text deltas.
+    ///
+    /// The default implementation falls back to [`complete`](Self::complete)
+    /// and emits the whole response as a single delta, so providers without a
+    /// native SSE endpoint still satisfy the trait.
+    async fn complete_stream(
+        &self,
+        system: &str,
+        task: &str,
+    ) -> Result<CompletionStream, ProviderError> {
+        let completion = self.complete(system, task).await?;
+        Ok(Box::pin(stream::once(async move { Ok(completion.content) })))
+    }
+}
@@ -0,0 +1,101 @@
+use futures::{Stream, StreamExt};
+
+use super::provider::{CompletionStream, ProviderError};
+
+/// What a decoded SSE line means to the caller's per-provider parser.
+pub(crate) enum LineAction {
+    /// Emit a text delta downstream.
+    Emit(String),
+    /// Ignore this line (blank line, keep-alive comment, unrelated event).
+    Skip,
+    /// Terminate the stream (e.g. `[DONE]` or `message_stop`).
+    Stop,
+}
+
+/// Decode a `text/event-stream` byte stream into completion deltas, delegating
+/// the per-line interpretation to `on_line`.
+///
+/// This owns the part every provider shares: buffer raw bytes and split on
+/// newline boundaries so a multi-byte codepoint straddling two network chunks
+/// is only decoded once its whole line has arrived. The differences between
+/// providers (`data: [DONE]` vs a `message_stop` event, where the delta text
+/// lives in the JSON) stay in each provider's `on_line` closure.
+///
+/// Kept generic over the chunk/error types so it can be driven from a canned
+/// in-memory body in tests as well as from a live `reqwest` response.
+pub(crate) fn decode_sse<S, C, E, F>(bytes: S, mut on_line: F) -> CompletionStream
+where
+    S: Stream<Item = Result<C, E>> + Send + 'static,
+    C: AsRef<[u8]> + Send,
+    E: Into<ProviderError> + Send + 'static,
+    F: FnMut(&str) -> Result<LineAction, ProviderError> + Send + 'static,
+{
+    let stream = async_stream::try_stream! {
+        let mut bytes = Box::pin(bytes);
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(chunk) = bytes.next().await {
+            let chunk = chunk.map_err(Into::into)?;
+            buffer.extend_from_slice(chunk.as_ref());
+
+            while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+                let raw: Vec<u8> = buffer.drain(..=newline).collect();
+                let line = String::from_utf8_lossy(&raw[..raw.len() - 1]).trim().to_string();
+                match on_line(&line)? {
+                    LineAction::Emit(delta) => yield delta,
+                    LineAction::Skip => {}
+                    LineAction::Stop => return,
+                }
+            }
+        }
+    };
+
+    Box::pin(stream)
+}
+
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+
+    /// Feed `body` through [`decode_sse`] in tiny fixed-size chunks so splits
+    /// can land inside a line or inside a multi-byte codepoint, then collect
+    /// the emitted deltas.
+    pub(crate) async fn decode_in_chunks<F>(body: &str, chunk_size: usize, on_line: F) -> Vec<String>
+    where
+        F: FnMut(&str) -> Result<LineAction, ProviderError> + Send + 'static,
+    {
+        let parts: Vec<Result<Vec<u8>, ProviderError>> = body
+            .as_bytes()
+            .chunks(chunk_size)
+            .map(|c| Ok(c.to_vec()))
+            .collect();
+        decode_sse(futures::stream::iter(parts), on_line)
+            .map(|r| r.unwrap())
+            .collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::decode_in_chunks;
+    use super::*;
+
+    /// A minimal parser that emits the payload of every `data:` line, so this
+    /// test isolates the shared newline/codepoint buffering from any
+    /// provider-specific framing.
+    fn emit_data_lines(line: &str) -> Result<LineAction, ProviderError> {
+        match line.strip_prefix("data:") {
+            Some(data) => Ok(LineAction::Emit(data.trim().to_string())),
+            None => Ok(LineAction::Skip),
+        }
+    }
+
+    #[tokio::test]
+    async fn reassembles_lines_across_chunk_and_codepoint_boundaries() {
+        // "Hé" contains a 2-byte codepoint; 2-byte chunking splits it and the
+        // surrounding lines, so only the newline buffering makes this decode.
+        let body = "data: Hé\n\n: keep-alive\ndata: !\n";
+        let deltas = decode_in_chunks(body, 2, emit_data_lines).await;
+        assert_eq!(deltas, vec!["Hé".to_string(), "!".to_string()]);
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task;
+
+use crate::models::provider::{Completion, ProviderError};
+use crate::models::ModelProvider;
+
+/// A single node in a pipeline graph.
+///
+/// `task`/`system_prompt` may reference an upstream step's output with a
+/// `{uuid}` placeholder, which is substituted with that step's completion text
+/// before the step runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStep {
+    pub uuid: String,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    pub system_prompt: String,
+    pub task: String,
+}
+
+/// A declarative DAG of agent steps.
+///
+/// Deserialize one from JSON to declare workflows externally:
+///
+/// ```json
+/// { "steps": [ { "uuid": "a", "depends_on": [], "system_prompt": "...", "task": "..." } ] }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub steps: Vec<PipelineStep>,
+}
+
+/// The result of executing a single step.
+#[derive(Debug)]
+pub enum StepOutcome {
+    /// The step ran and produced a completion.
+    Completed(Completion),
+    /// The step ran but the provider returned an error.
+    Failed(ProviderError),
+    /// The step never ran because an upstream step errored or was skipped.
+    Skipped { reason: String },
+}
+
+/// Errors that prevent the pipeline from executing at all.
+#[derive(Debug)]
+pub enum PipelineError {
+    /// A step depends on a `uuid` that is not present in the graph.
+    UnknownDependency { step: String, dependency: String },
+    /// The graph contains a cycle and cannot be topologically ordered.
+    Cycle { remaining: Vec<String> },
+}
+
+impl std::fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineError::UnknownDependency { step, dependency } => {
+                write!(f, "step '{}' depends on unknown step '{}'", step, dependency)
+            }
+            PipelineError::Cycle { remaining } => {
+                write!(f, "pipeline graph has a cycle among: {}", remaining.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl Pipeline {
+    /// Parse a pipeline from a JSON document.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Execute the graph, running each independent layer concurrently and
+    /// feeding upstream completions into downstream prompts.
+    ///
+    /// Returns a map of step `uuid` -> [`StepOutcome`]. A branch whose upstream
+    /// errored is short-circuited to [`StepOutcome::Skipped`] rather than run.
+    pub async fn execute(
+        &self,
+        provider: Arc<dyn ModelProvider>,
+    ) -> Result<HashMap<String, StepOutcome>, PipelineError> {
+        let layers = self.topological_layers()?;
+        let mut outcomes: HashMap<String, StepOutcome> = HashMap::new();
+
+        for layer in layers {
+            // Partition the layer into runnable steps and those short-circuited
+            // by a failed/skipped upstream.
+            let mut runnable = Vec::new();
+            for uuid in layer {
+                let step = self.step(&uuid).expect("layer refers to a known step");
+                if let Some(reason) = self.blocked_reason(step, &outcomes) {
+                    outcomes.insert(uuid, StepOutcome::Skipped { reason });
+                } else {
+                    runnable.push(step.clone());
+                }
+            }
+
+            if runnable.is_empty() {
+                continue;
+            }
+
+            let (tx, mut rx) = mpsc::channel(runnable.len());
+            for step in runnable {
+                let provider = Arc::clone(&provider);
+                let tx = tx.clone();
+                let (system, task) = resolve_prompts(&step, &outcomes);
+
+                task::spawn(async move {
+                    let result = provider.complete(&system, &task).await;
+                    if tx.send((step.uuid.clone(), result)).await.is_err() {
+                        warn!("Failed to send pipeline step result for '{}'", step.uuid);
+                    }
+                });
+            }
+            drop(tx);
+
+            while let Some((uuid, result)) = rx.recv().await {
+                let outcome = match result {
+                    Ok(completion) => {
+                        info!("Pipeline step '{}' completed", uuid);
+                        StepOutcome::Completed(completion)
+                    }
+                    Err(e) => {
+                        warn!("Pipeline step '{}' failed: {}", uuid, e);
+                        StepOutcome::Failed(e)
+                    }
+                };
+                outcomes.insert(uuid, outcome);
+            }
+        }
+
+        Ok(outcomes)
+    }
+
+    fn step(&self, uuid: &str) -> Option<&PipelineStep> {
+        self.steps.iter().find(|s| s.uuid == uuid)
+    }
+
+    /// Return a skip reason if any dependency of `step` did not complete.
+    fn blocked_reason(
+        &self,
+        step: &PipelineStep,
+        outcomes: &HashMap<String, StepOutcome>,
+    ) -> Option<String> {
+        for dep in &step.depends_on {
+            match outcomes.get(dep) {
+                Some(StepOutcome::Completed(_)) => {}
+                Some(StepOutcome::Failed(_)) => {
+                    return Some(format!("upstream step '{}' failed", dep))
+                }
+                Some(StepOutcome::Skipped { .. }) => {
+                    return Some(format!("upstream step '{}' was skipped", dep))
+                }
+                None => return Some(format!("upstream step '{}' did not run", dep)),
+            }
+        }
+        None
+    }
+
+    /// Kahn's algorithm, grouping ready nodes into concurrency layers.
+    fn topological_layers(&self) -> Result<Vec<Vec<String>>, PipelineError> {
+        let known: HashSet<&str> = self.steps.iter().map(|s| s.uuid.as_str()).collect();
+        let mut in_degree: HashMap<&str, usize> = HashMap::new();
+        for step in &self.steps {
+            in_degree.entry(step.uuid.as_str()).or_insert(0);
+            for dep in &step.depends_on {
+                if !known.contains(dep.as_str()) {
+                    return Err(PipelineError::UnknownDependency {
+                        step: step.uuid.clone(),
+                        dependency: dep.clone(),
+                    });
+                }
+                *in_degree.entry(step.uuid.as_str()).or_insert(0) += 1;
+            }
+        }
+
+        let mut layers = Vec::new();
+        let mut resolved = 0usize;
+        while resolved < self.steps.len() {
+            let mut layer: Vec<String> = in_degree
+                .iter()
+                .filter(|(_, &deg)| deg == 0)
+                .map(|(&uuid, _)| uuid.to_string())
+                .collect();
+            layer.sort();
+
+            if layer.is_empty() {
+                let remaining = in_degree.keys().map(|s| s.to_string()).collect();
+                return Err(PipelineError::Cycle { remaining });
+            }
+
+            for uuid in &layer {
+                in_degree.remove(uuid.as_str());
+            }
+            // Decrement in-degree of steps depending on this layer.
+            for step in &self.steps {
+                if in_degree.contains_key(step.uuid.as_str()) {
+                    let satisfied = step
+                        .depends_on
+                        .iter()
+                        .filter(|dep| layer.contains(dep))
+                        .count();
+                    if satisfied > 0 {
+                        let deg = in_degree.get_mut(step.uuid.as_str()).unwrap();
+                        *deg -= satisfied;
+                    }
+                }
+            }
+
+            resolved += layer.len();
+            layers.push(layer);
+        }
+
+        Ok(layers)
+    }
+}
+
+/// Substitute `{dep_uuid}` placeholders in the step's prompts with the text of
+/// each completed upstream step.
+fn resolve_prompts(step: &PipelineStep, outcomes: &HashMap<String, StepOutcome>) -> (String, String) {
+    let mut system = step.system_prompt.clone();
+    let mut task = step.task.clone();
+
+    for dep in &step.depends_on {
+        if let Some(StepOutcome::Completed(completion)) = outcomes.get(dep) {
+            let placeholder = format!("{{{}}}", dep);
+            system = system.replace(&placeholder, &completion.content);
+            task = task.replace(&placeholder, &completion.content);
+        }
+    }
+
+    (system, task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    /// Echoes the resolved task back as its completion, but fails any step
+    /// whose task contains `"FAIL"` so skip propagation can be exercised.
+    struct EchoProvider;
+
+    #[async_trait]
+    impl ModelProvider for EchoProvider {
+        fn provider_name(&self) -> &str {
+            "echo"
+        }
+
+        fn model(&self) -> &str {
+            "echo-model"
+        }
+
+        async fn complete(&self, _system: &str, task: &str) -> Result<Completion, ProviderError> {
+            if task.contains("FAIL") {
+                return Err(ProviderError::Parse("forced failure".to_string()));
+            }
+            Ok(Completion {
+                provider: "echo".to_string(),
+                model: "echo-model".to_string(),
+                content: task.to_string(),
+                prompt_tokens: None,
+                completion_tokens: None,
+            })
+        }
+    }
+
+    fn step(uuid: &str, depends_on: &[&str], task: &str) -> PipelineStep {
+        PipelineStep {
+            uuid: uuid.to_string(),
+            depends_on: depends_on.iter().map(|s| s.to_string()).collect(),
+            system_prompt: "sys".to_string(),
+            task: task.to_string(),
+        }
+    }
+
+    #[test]
+    fn diamond_graph_layers_by_depth() {
+        // a -> {b, c} -> d
+        let pipeline = Pipeline {
+            steps: vec![
+                step("a", &[], "root"),
+                step("b", &["a"], "left"),
+                step("c", &["a"], "right"),
+                step("d", &["b", "c"], "join"),
+            ],
+        };
+        let layers = pipeline.topological_layers().expect("acyclic");
+        assert_eq!(layers, vec![vec!["a"], vec!["b", "c"], vec!["d"]]);
+    }
+
+    #[test]
+    fn cycle_is_rejected() {
+        let pipeline = Pipeline {
+            steps: vec![step("a", &["b"], "x"), step("b", &["a"], "y")],
+        };
+        match pipeline.topological_layers() {
+            Err(PipelineError::Cycle { mut remaining }) => {
+                remaining.sort();
+                assert_eq!(remaining, vec!["a", "b"]);
+            }
+            other => panic!("expected a cycle error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_dependency_is_rejected() {
+        let pipeline = Pipeline {
+            steps: vec![step("a", &["ghost"], "x")],
+        };
+        match pipeline.topological_layers() {
+            Err(PipelineError::UnknownDependency { step, dependency }) => {
+                assert_eq!(step, "a");
+                assert_eq!(dependency, "ghost");
+            }
+            other => panic!("expected an unknown-dependency error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn diamond_executes_and_substitutes_upstream() {
+        let pipeline = Pipeline {
+            steps: vec![
+                step("a", &[], "seed"),
+                step("b", &["a"], "b saw {a}"),
+                step("c", &["a"], "c saw {a}"),
+                step("d", &["b", "c"], "d saw {b} and {c}"),
+            ],
+        };
+        let outcomes = pipeline.execute(Arc::new(EchoProvider)).await.expect("runs");
+
+        match outcomes.get("d") {
+            Some(StepOutcome::Completed(c)) => {
+                assert_eq!(c.content, "d saw b saw seed and c saw seed");
+            }
+            other => panic!("expected d to complete, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn failure_skips_downstream_branch() {
+        // a fails -> b (depends a) is skipped -> c (depends b) is skipped too.
+        let pipeline = Pipeline {
+            steps: vec![
+                step("a", &[], "please FAIL"),
+                step("b", &["a"], "uses {a}"),
+                step("c", &["b"], "uses {b}"),
+            ],
+        };
+        let outcomes = pipeline.execute(Arc::new(EchoProvider)).await.expect("runs");
+
+        assert!(matches!(outcomes.get("a"), Some(StepOutcome::Failed(_))));
+        assert!(matches!(outcomes.get("b"), Some(StepOutcome::Skipped { .. })));
+        assert!(matches!(outcomes.get("c"), Some(StepOutcome::Skipped { .. })));
+    }
+}
@@ -0,0 +1,349 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::Utc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task;
+use uuid::Uuid;
+
+use crate::file_utils::create_file;
+use crate::models::provider::Completion;
+use crate::models::ModelProvider;
+
+/// Where benchmark reports are written and how the run is labelled.
+#[derive(Debug, Clone)]
+pub struct ReportConfig {
+    /// Folder the JSON report is written into (created if absent).
+    pub report_folder: String,
+    /// Provider name recorded in the report for cross-run comparison.
+    pub provider: String,
+    /// Model name recorded in the report.
+    pub model: String,
+    /// Maximum retries per task on a retryable error; the recorded retry count
+    /// is the number of extra attempts actually made.
+    pub max_retries: u32,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            report_folder: "reports".to_string(),
+            provider: String::new(),
+            model: String::new(),
+            max_retries: 3,
+        }
+    }
+}
+
+/// Per-task measurement captured during a benchmark run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskMetric {
+    pub task: usize,
+    pub success: bool,
+    pub latency_ms: u128,
+    pub retries: u32,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Aggregate statistics derived from the per-task metrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregateStats {
+    pub total_tasks: usize,
+    pub successes: usize,
+    pub failures: usize,
+    pub error_rate: f64,
+    pub p50_latency_ms: u128,
+    pub p90_latency_ms: u128,
+    pub p99_latency_ms: u128,
+    pub total_prompt_tokens: u64,
+    pub total_completion_tokens: u64,
+    pub total_tokens: u64,
+    pub throughput_rps: f64,
+    pub wall_clock_ms: u128,
+}
+
+/// Environment metadata recorded so reports are comparable across machines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvMetadata {
+    pub hostname: String,
+    pub cpu_count: usize,
+    pub crate_version: String,
+    pub timestamp: String,
+}
+
+impl EnvMetadata {
+    fn capture() -> Self {
+        Self {
+            // Read the real kernel hostname rather than the `$HOSTNAME` shell
+            // variable, which is not exported to child processes on Linux and
+            // is absent on Windows — so cross-machine reports stay comparable.
+            hostname: hostname::get()
+                .ok()
+                .and_then(|h| h.into_string().ok())
+                .unwrap_or_else(|| "unknown".to_string()),
+            cpu_count: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// A full, self-describing report for one benchmark invocation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub run_id: String,
+    pub provider: String,
+    pub model: String,
+    pub concurrency: usize,
+    pub stats: AggregateStats,
+    pub env: EnvMetadata,
+    pub tasks: Vec<TaskMetric>,
+}
+
+/// Run `n` concurrent completions against `provider`, measuring each one, and
+/// return a [`Report`] that is also persisted as JSON under
+/// `config.report_folder`.
+///
+/// Each run is tagged with a fresh UUID so reports gathered across models,
+/// providers, and concurrency levels can be diffed as a performance-regression
+/// signal rather than re-parsed from the ad-hoc response log.
+pub async fn benchmark_swarm(
+    provider: Arc<dyn ModelProvider>,
+    system: &str,
+    task: &str,
+    n: usize,
+    config: &ReportConfig,
+) -> Report {
+    let (tx, mut rx) = mpsc::channel(n);
+    let wall_start = Instant::now();
+
+    for i in 0..n {
+        let provider = Arc::clone(&provider);
+        let tx = tx.clone();
+        let system = system.to_string();
+        let task_prompt = task.to_string();
+        let max_retries = config.max_retries;
+
+        task::spawn(async move {
+            let started = Instant::now();
+            let (result, attempts) =
+                benchmark_with_retry(provider.as_ref(), &system, &task_prompt, i + 1, max_retries)
+                    .await;
+            let latency_ms = started.elapsed().as_millis();
+            // `attempts` counts every call; retries are the extra ones.
+            let retries = attempts.saturating_sub(1);
+
+            let metric = match result {
+                Ok(Completion { prompt_tokens, completion_tokens, .. }) => TaskMetric {
+                    task: i + 1,
+                    success: true,
+                    latency_ms,
+                    retries,
+                    prompt_tokens,
+                    completion_tokens,
+                    error: None,
+                },
+                Err(e) => TaskMetric {
+                    task: i + 1,
+                    success: false,
+                    latency_ms,
+                    retries,
+                    prompt_tokens: None,
+                    completion_tokens: None,
+                    error: Some(format!("{}", e)),
+                },
+            };
+
+            if tx.send(metric).await.is_err() {
+                warn!("Failed to send task metric to the receiver");
+            }
+        });
+    }
+
+    drop(tx);
+
+    let mut tasks = Vec::with_capacity(n);
+    while let Some(metric) = rx.recv().await {
+        tasks.push(metric);
+    }
+    tasks.sort_by_key(|m| m.task);
+
+    let wall_clock_ms = wall_start.elapsed().as_millis();
+    let stats = aggregate(&tasks, wall_clock_ms);
+
+    let report = Report {
+        run_id: Uuid::new_v4().to_string(),
+        provider: config.provider.clone(),
+        model: config.model.clone(),
+        concurrency: n,
+        stats,
+        env: EnvMetadata::capture(),
+        tasks,
+    };
+
+    // Persist the report; failure to write is logged but non-fatal so the
+    // in-memory report is still returned to the caller.
+    let serialized = serde_json::to_string_pretty(&report)
+        .unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e));
+    let file_name = format!("{}.json", report.run_id);
+    if let Err(e) = create_file(&config.report_folder, &file_name, &serialized).await {
+        warn!("Failed to write report {}: {}", file_name, e);
+    } else {
+        info!("Wrote benchmark report {}/{}", config.report_folder, file_name);
+    }
+
+    report
+}
+
+/// Run one completion, retrying retryable failures with the shared backoff
+/// schedule, and return the final result alongside the number of attempts made.
+///
+/// This mirrors the swarm executor's retry accounting so the benchmark records
+/// the real attempt count rather than a placeholder.
+async fn benchmark_with_retry(
+    provider: &dyn ModelProvider,
+    system: &str,
+    task: &str,
+    task_id: usize,
+    max_retries: u32,
+) -> (Result<Completion, crate::models::provider::ProviderError>, u32) {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match provider.complete(system, task).await {
+            Ok(completion) => return (Ok(completion), attempt),
+            Err(e) => {
+                if e.is_retryable() && attempt <= max_retries {
+                    let delay = crate::backoff_delay(attempt, e.retry_after(), task_id as u64);
+                    warn!("Benchmark attempt {} failed ({}); retrying in {:?}", attempt, e, delay);
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                return (Err(e), attempt);
+            }
+        }
+    }
+}
+
+/// Collapse per-task metrics into aggregate statistics.
+fn aggregate(tasks: &[TaskMetric], wall_clock_ms: u128) -> AggregateStats {
+    let total_tasks = tasks.len();
+    let successes = tasks.iter().filter(|m| m.success).count();
+    let failures = total_tasks - successes;
+
+    let mut latencies: Vec<u128> = tasks.iter().map(|m| m.latency_ms).collect();
+    latencies.sort_unstable();
+
+    let total_prompt_tokens: u64 = tasks.iter().filter_map(|m| m.prompt_tokens).sum();
+    let total_completion_tokens: u64 = tasks.iter().filter_map(|m| m.completion_tokens).sum();
+
+    let throughput_rps = if wall_clock_ms > 0 {
+        successes as f64 / (wall_clock_ms as f64 / 1000.0)
+    } else {
+        0.0
+    };
+
+    AggregateStats {
+        total_tasks,
+        successes,
+        failures,
+        error_rate: if total_tasks > 0 {
+            failures as f64 / total_tasks as f64
+        } else {
+            0.0
+        },
+        p50_latency_ms: percentile(&latencies, 50.0),
+        p90_latency_ms: percentile(&latencies, 90.0),
+        p99_latency_ms: percentile(&latencies, 99.0),
+        total_prompt_tokens,
+        total_completion_tokens,
+        total_tokens: total_prompt_tokens + total_completion_tokens,
+        throughput_rps,
+        wall_clock_ms,
+    }
+}
+
+/// Nearest-rank percentile over a pre-sorted slice of latencies.
+fn percentile(sorted: &[u128], pct: f64) -> u128 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (pct / 100.0 * sorted.len() as f64).ceil() as usize;
+    let idx = rank.saturating_sub(1).min(sorted.len() - 1);
+    sorted[idx]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::provider::ProviderError;
+    use async_trait::async_trait;
+
+    /// A provider that returns a fixed completion without any network I/O.
+    struct StubProvider;
+
+    #[async_trait]
+    impl ModelProvider for StubProvider {
+        fn provider_name(&self) -> &str {
+            "stub"
+        }
+
+        fn model(&self) -> &str {
+            "stub-model"
+        }
+
+        async fn complete(&self, _system: &str, _task: &str) -> Result<Completion, ProviderError> {
+            Ok(Completion {
+                provider: "stub".to_string(),
+                model: "stub-model".to_string(),
+                content: "ok".to_string(),
+                prompt_tokens: Some(3),
+                completion_tokens: Some(5),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn benchmark_swarm_aggregates_stub_runs() {
+        let dir = std::env::temp_dir().join(format!("swarm-report-test-{}", Uuid::new_v4()));
+        let config = ReportConfig {
+            report_folder: dir.to_string_lossy().into_owned(),
+            provider: "stub".to_string(),
+            model: "stub-model".to_string(),
+            max_retries: 0,
+        };
+
+        let report = benchmark_swarm(Arc::new(StubProvider), "sys", "task", 4, &config).await;
+
+        assert_eq!(report.stats.total_tasks, 4);
+        assert_eq!(report.stats.successes, 4);
+        assert_eq!(report.stats.failures, 0);
+        assert_eq!(report.stats.error_rate, 0.0);
+        // 4 tasks * (3 prompt + 5 completion) tokens.
+        assert_eq!(report.stats.total_prompt_tokens, 12);
+        assert_eq!(report.stats.total_completion_tokens, 20);
+        assert_eq!(report.stats.total_tokens, 32);
+        assert_eq!(report.tasks.len(), 4);
+        assert!(report.tasks.iter().all(|t| t.success && t.retries == 0));
+
+        // The report is persisted under its run id.
+        let written = dir.join(format!("{}.json", report.run_id));
+        assert!(written.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn percentile_nearest_rank() {
+        let sorted = [10u128, 20, 30, 40, 50];
+        assert_eq!(percentile(&sorted, 50.0), 30);
+        assert_eq!(percentile(&sorted, 90.0), 50);
+        assert_eq!(percentile(&sorted, 99.0), 50);
+        assert_eq!(percentile(&[], 50.0), 0);
+    }
+}